@@ -1,28 +1,355 @@
 use tokio::fs::{File, OpenOptions, remove_file};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use std::collections::HashMap;
+use tokio::io::{AsyncBufReadExt, AsyncSeekExt, AsyncWriteExt, BufReader};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::SeekFrom;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, Mutex};
 use std::cmp::Reverse;
+use dashmap::DashMap;
+
+/// Controls how raw whitespace-separated tokens become the words that get
+/// counted: whether to trim attached punctuation, whether to case-fold, and
+/// which tokens to drop as stop words.
+#[derive(Debug, Clone)]
+struct TokenizerConfig {
+    strip_punctuation: bool,
+    fold_case: bool,
+    stop_words: Arc<HashSet<String>>,
+}
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let file_path = "shakes.txt";
-    let num_map_workers = 4;
-    let num_reduce_workers = 4;
-    let num_chunks = 4;
+impl Default for TokenizerConfig {
+    fn default() -> Self {
+        TokenizerConfig {
+            strip_punctuation: false,
+            fold_case: true,
+            stop_words: Arc::new(HashSet::new()),
+        }
+    }
+}
+
+/// Normalizes a single whitespace-split token per `config`, returning `None`
+/// if the token should be dropped entirely (empty after trimming, or a stop
+/// word).
+fn normalize_token(word: &str, config: &TokenizerConfig) -> Option<String> {
+    let trimmed = if config.strip_punctuation {
+        word.trim_matches(|c: char| !c.is_alphanumeric())
+    } else {
+        word
+    };
+
+    if trimmed.is_empty() {
+        return None;
+    }
 
-    // Read the file and split into chunks
-    let chunks = split_file(file_path, num_chunks).await?;
+    let token = if config.fold_case {
+        trimmed.to_lowercase()
+    } else {
+        trimmed.to_string()
+    };
+
+    if config.stop_words.contains(&token) {
+        return None;
+    }
 
-    // Map phase
-    let map_results = map_phase(&chunks, num_map_workers).await?;
+    Some(token)
+}
+
+/// Loads a newline-separated stop-word list, folded by `fold_case` so it
+/// matches whatever case policy `normalize_token` applies to tokens -- a
+/// stop-word list lowercased unconditionally would silently stop matching
+/// anything once `--no-case-fold` keeps tokens in their original case.
+async fn load_stop_words(path: &str, fold_case: bool) -> Result<HashSet<String>, std::io::Error> {
+    let file = File::open(path).await?;
+    let reader = BufReader::new(file);
+    let mut lines = reader.lines();
+    let mut stop_words = HashSet::new();
+
+    while let Some(line) = lines.next_line().await? {
+        let word = line.trim();
+        if !word.is_empty() {
+            let word = if fold_case { word.to_lowercase() } else { word.to_string() };
+            stop_words.insert(word);
+        }
+    }
+
+    Ok(stop_words)
+}
+
+/// Capacity of the bounded channels linking producer -> map -> reduce, as a
+/// multiple of the worker pool feeding from the channel. Bounds how much
+/// work can queue up before backpressure stalls the upstream stage.
+const CHANNEL_CAPACITY_PER_WORKER: usize = 2;
+
+/// Path of the on-disk progress journal that makes a run resumable after a
+/// crash. Each line is one committed fact -- a chunk becoming `Mapped` to an
+/// intermediate file, or becoming `Reduced` with the word counts that chunk
+/// itself contributed -- appended as it happens rather than rewriting the
+/// whole database, so persisting one chunk's commit costs O(that chunk),
+/// not O(every chunk committed so far).
+const PROGRESS_DB_PATH: &str = "progress.json";
+
+/// State of a single chunk of work, keyed by chunk index, mirroring an
+/// incremental-build database. `Reduced` carries exactly the word counts
+/// that chunk folded in, so the run total can be reconstructed from the
+/// chunks known to be committed instead of from a snapshot of a live,
+/// concurrently-mutated map.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum ChunkState {
+    Pending,
+    Mapped { file_name: String },
+    Reduced { counts: HashMap<String, usize> },
+}
+
+/// A single line of the progress journal: one chunk's state as of the
+/// commit that appended it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProgressEntry {
+    chunk_index: usize,
+    state: ChunkState,
+}
+
+/// In-memory replay of the progress journal.
+#[derive(Debug, Default)]
+struct ProgressDb {
+    chunks: HashMap<usize, ChunkState>,
+}
+
+impl ProgressDb {
+    fn state_of(&self, chunk_index: usize) -> ChunkState {
+        self.chunks.get(&chunk_index).cloned().unwrap_or(ChunkState::Pending)
+    }
+
+    /// Reconstructs the run's total word counts from chunks durably marked
+    /// `Reduced`. A chunk whose commit never finished simply isn't in this
+    /// map yet, so it can't contribute twice, or contribute at all, to the
+    /// total -- unlike trusting a cached snapshot that other chunks may
+    /// have still been mutating when it was taken.
+    fn accumulated(&self) -> HashMap<String, usize> {
+        let mut totals = HashMap::new();
+        for state in self.chunks.values() {
+            if let ChunkState::Reduced { counts } = state {
+                for (word, count) in counts {
+                    *totals.entry(word.clone()).or_insert(0) += count;
+                }
+            }
+        }
+        totals
+    }
+}
+
+/// Replays the progress journal, or starts a fresh one if none exists yet.
+async fn load_progress_db(path: &str) -> Result<ProgressDb, std::io::Error> {
+    let contents = match tokio::fs::read_to_string(path).await {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(ProgressDb::default()),
+        Err(e) => return Err(e),
+    };
+
+    let mut db = ProgressDb::default();
+    for line in contents.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: ProgressEntry = serde_json::from_str(line)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        db.chunks.insert(entry.chunk_index, entry.state);
+    }
+    Ok(db)
+}
+
+/// Appends one chunk's committed state to the progress journal. A crash
+/// mid-run loses at most the chunks that were in flight, and the write never
+/// touches the facts already on disk for chunks committed earlier.
+async fn append_progress_entry(
+    path: &str,
+    chunk_index: usize,
+    state: &ChunkState,
+) -> Result<(), std::io::Error> {
+    let entry = ProgressEntry { chunk_index, state: state.clone() };
+    let mut line = serde_json::to_string(&entry)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    line.push('\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(path).await?;
+    file.write_all(line.as_bytes()).await
+}
 
-    // Reduce phase
-    let final_result = reduce_phase(map_results.clone(), num_reduce_workers).await?;
+/// Clears the progress journal after a successful run. Without this, the
+/// next invocation -- on the same input or a different one -- would find
+/// every chunk already `Reduced` and silently emit the stale accumulated
+/// result instead of actually processing anything.
+async fn reset_progress_db(path: &str) -> Result<(), std::io::Error> {
+    match remove_file(path).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Word count map used on the hot per-chunk counting path, backed by `ahash`
+/// for faster hashing than the default SipHash.
+type WordMap = ahash::AHashMap<String, usize>;
+
+/// A contiguous, newline-aligned byte range `[start, end)` within the input
+/// file that one map worker reads directly, without the file being
+/// buffered into memory up front.
+type ChunkRange = (u64, u64);
+
+/// Output format selected via `--format json|text`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "text" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Default number of bytes per chunk when `--chunks` isn't given, chosen to
+/// keep a fixed worker pool busy with many small chunks rather than tying
+/// chunk count to worker count.
+const DEFAULT_BYTES_PER_CHUNK: u64 = 1_000_000;
+
+/// Parses `--format json|text` out of the process args, defaulting to `Text`.
+fn parse_format(args: &[String]) -> OutputFormat {
+    for i in 0..args.len() {
+        if args[i] == "--format" {
+            if let Some(value) = args.get(i + 1) {
+                if let Some(format) = OutputFormat::from_arg(value) {
+                    return format;
+                }
+                eprintln!("Unrecognized --format value '{}', defaulting to text", value);
+            }
+        }
+    }
+    OutputFormat::Text
+}
+
+/// Parses `<flag> <n>` out of the process args as a positive `usize`.
+fn parse_usize_flag(args: &[String], flag: &str) -> Option<usize> {
+    for i in 0..args.len() {
+        if args[i] == flag {
+            if let Some(value) = args.get(i + 1) {
+                match value.parse::<usize>() {
+                    Ok(n) if n > 0 => return Some(n),
+                    _ => eprintln!("Unrecognized {} value '{}', ignoring", flag, value),
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Checks whether a value-less flag (e.g. `--strip-punctuation`) is present.
+fn has_flag(args: &[String], flag: &str) -> bool {
+    args.iter().any(|arg| arg == flag)
+}
+
+/// Parses `<flag> <value>` out of the process args as a plain string.
+fn parse_str_flag<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    for i in 0..args.len() {
+        if args[i] == flag {
+            return args.get(i + 1).map(String::as_str);
+        }
+    }
+    None
+}
+
+/// Builds the tokenizer policy from `--strip-punctuation`, `--no-case-fold`,
+/// and `--stop-words <path>`.
+async fn parse_tokenizer_config(args: &[String]) -> Result<TokenizerConfig, std::io::Error> {
+    let fold_case = !has_flag(args, "--no-case-fold");
+    let stop_words = match parse_str_flag(args, "--stop-words") {
+        Some(path) => load_stop_words(path, fold_case).await?,
+        None => HashSet::new(),
+    };
+
+    Ok(TokenizerConfig {
+        strip_punctuation: has_flag(args, "--strip-punctuation"),
+        fold_case,
+        stop_words: Arc::new(stop_words),
+    })
+}
+
+/// Defaults the worker pool size to the detected CPU count, falling back to
+/// 4 if it can't be determined.
+fn default_worker_count() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+}
+
+/// Computes how many `bytes_per_chunk`-sized chunks the file should be split
+/// into, independent of the worker pool size.
+async fn chunk_count_for_file(file_path: &str, bytes_per_chunk: u64) -> Result<usize, std::io::Error> {
+    let file_len = File::open(file_path).await?.metadata().await?.len();
+    let bytes_per_chunk = bytes_per_chunk.max(1);
+    let chunks = file_len.div_ceil(bytes_per_chunk);
+    Ok(chunks.max(1) as usize)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let file_path = "shakes.txt";
+    let args: Vec<String> = std::env::args().collect();
+    let format = parse_format(&args);
+
+    let default_workers = default_worker_count();
+    let num_map_workers = parse_usize_flag(&args, "--workers").unwrap_or(default_workers);
+    let num_reduce_workers = parse_usize_flag(&args, "--workers").unwrap_or(default_workers);
+    let bytes_per_chunk = parse_usize_flag(&args, "--chunk-bytes")
+        .map(|n| n as u64)
+        .unwrap_or(DEFAULT_BYTES_PER_CHUNK);
+    let num_chunks = match parse_usize_flag(&args, "--chunks") {
+        Some(n) => n,
+        None => chunk_count_for_file(file_path, bytes_per_chunk).await?,
+    };
+    let tokenizer_config = parse_tokenizer_config(&args).await?;
+
+    // Load (or start) the progress database so a crash mid-run doesn't force
+    // remapping or re-reducing chunks already durably recorded as done.
+    let progress_db = Arc::new(Mutex::new(load_progress_db(PROGRESS_DB_PATH).await?));
+    let initial_accumulated = progress_db.lock().await.accumulated();
+
+    // Producer -> map -> reduce are linked by bounded channels so reduction
+    // of early chunks overlaps with mapping of later ones, instead of the
+    // reduce phase waiting for the whole map phase to finish.
+    let (range_tx, range_rx) = mpsc::channel(num_map_workers * CHANNEL_CAPACITY_PER_WORKER);
+    let (file_tx, file_rx) = mpsc::channel(num_reduce_workers * CHANNEL_CAPACITY_PER_WORKER);
+
+    let producer_handle = tokio::spawn(produce_ranges(
+        file_path.to_string(),
+        num_chunks,
+        range_tx,
+        file_tx.clone(),
+        Arc::clone(&progress_db),
+    ));
+    let map_handles = map_phase(
+        file_path,
+        range_rx,
+        file_tx,
+        num_map_workers,
+        format,
+        tokenizer_config,
+        Arc::clone(&progress_db),
+    );
+    let reduce_handle = tokio::spawn(reduce_phase(file_rx, num_reduce_workers, initial_accumulated, progress_db));
+
+    producer_handle.await??;
+    for handle in map_handles {
+        handle.await??;
+    }
+    let (final_result, map_results) = reduce_handle.await??;
 
     // Write final result
-    write_final_result(&final_result).await?;
+    write_final_result(&final_result, format).await?;
 
     // Print top 10 words
     print_top_words(&final_result, 10);
@@ -30,49 +357,120 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Clean up intermediate files
     cleanup_intermediate_files(&map_results).await?;
 
+    // The run finished, so the next invocation should start fresh rather
+    // than see every chunk as already Reduced.
+    reset_progress_db(PROGRESS_DB_PATH).await?;
+
     Ok(())
 }
 
-async fn split_file(file_path: &str, num_chunks: usize) -> Result<Vec<String>, std::io::Error> {
-    let file = File::open(file_path).await?;
-    let reader = BufReader::new(file);
-    let mut lines = reader.lines();
+/// Computes newline-aligned byte ranges one at a time and sends each as soon
+/// as it's ready, rather than materializing the full chunk list up front.
+/// Chunks already mapped or reduced in a prior run are skipped: an
+/// already-mapped chunk's intermediate file is handed straight to the
+/// reduce stage instead of being remapped.
+async fn produce_ranges(
+    file_path: String,
+    num_chunks: usize,
+    range_tx: mpsc::Sender<(usize, ChunkRange)>,
+    file_tx: mpsc::Sender<(usize, String)>,
+    progress_db: Arc<Mutex<ProgressDb>>,
+) -> Result<(), std::io::Error> {
+    let mut file = File::open(&file_path).await?;
+    let file_len = file.metadata().await?.len();
+
+    let mut start = 0u64;
+    for chunk_index in 0..num_chunks {
+        let end = if chunk_index == num_chunks - 1 {
+            file_len
+        } else {
+            let approx = file_len * (chunk_index as u64 + 1) / num_chunks as u64;
+            next_line_boundary(&mut file, approx, file_len).await?
+        };
+        let range = (start, end);
+        start = end;
+
+        match progress_db.lock().await.state_of(chunk_index) {
+            ChunkState::Reduced { .. } => continue,
+            ChunkState::Mapped { file_name } => {
+                if file_tx.send((chunk_index, file_name)).await.is_err() {
+                    break; // no reduce workers left to receive
+                }
+            }
+            ChunkState::Pending => {
+                if range_tx.send((chunk_index, range)).await.is_err() {
+                    break; // no map workers left to receive
+                }
+            }
+        }
+    }
 
-    let mut chunks = vec![String::new(); num_chunks];
-    let mut chunk_index = 0;
+    Ok(())
+}
 
-    while let Some(line) = lines.next_line().await? {
-        chunks[chunk_index].push_str(&line);
-        chunks[chunk_index].push('\n');
-        chunk_index = (chunk_index + 1) % num_chunks;
+/// Seeks to `approx` and reads forward to the end of the line it lands in,
+/// so a chunk boundary never splits a word across two chunks.
+async fn next_line_boundary(file: &mut File, approx: u64, file_len: u64) -> Result<u64, std::io::Error> {
+    if approx == 0 || approx >= file_len {
+        return Ok(approx.min(file_len));
     }
 
-    Ok(chunks)
+    file.seek(SeekFrom::Start(approx)).await?;
+    let mut reader = BufReader::new(&mut *file);
+    let mut discarded = String::new();
+    let bytes_to_boundary = reader.read_line(&mut discarded).await?;
+
+    Ok((approx + bytes_to_boundary as u64).min(file_len))
 }
 
-async fn map_phase(chunks: &[String], num_workers: usize) -> Result<Vec<String>, std::io::Error> {
-    let chunk_queue = Arc::new(Mutex::new(chunks.to_vec()));
-    let results = Arc::new(Mutex::new(Vec::new()));
+/// Spawns `num_workers` map workers that pull chunk ranges off `range_rx` as
+/// soon as the producer sends them and push each completed intermediate
+/// filename onto `file_tx` for the reduce workers to pick up. Returns the
+/// worker handles immediately so mapping and reducing run concurrently.
+fn map_phase(
+    file_path: &str,
+    range_rx: mpsc::Receiver<(usize, ChunkRange)>,
+    file_tx: mpsc::Sender<(usize, String)>,
+    num_workers: usize,
+    format: OutputFormat,
+    tokenizer_config: TokenizerConfig,
+    progress_db: Arc<Mutex<ProgressDb>>,
+) -> Vec<tokio::task::JoinHandle<Result<(), std::io::Error>>> {
+    let range_rx = Arc::new(Mutex::new(range_rx));
+    let file_path = Arc::new(file_path.to_string());
+    let mut handles = Vec::with_capacity(num_workers);
 
-    let mut handles = vec![];
-
-    for worker_id in 0..num_workers {
-        let chunk_queue = Arc::clone(&chunk_queue);
-        let results = Arc::clone(&results);
+    for _ in 0..num_workers {
+        let range_rx = Arc::clone(&range_rx);
+        let file_path = Arc::clone(&file_path);
+        let file_tx = file_tx.clone();
+        let progress_db = Arc::clone(&progress_db);
+        let tokenizer_config = tokenizer_config.clone();
 
         let handle = tokio::spawn(async move {
             loop {
-                let chunk = {
-                    let mut queue = chunk_queue.lock().await;
-                    queue.pop()
-                };
+                let chunk = { range_rx.lock().await.recv().await };
 
                 match chunk {
-                    Some(text) => {
-                        let word_counts = count_words(&text);
-                        let result = format!("map_{}.txt", worker_id);
-                        write_map_result(&result, &word_counts).await?;
-                        results.lock().await.push(result);
+                    Some((chunk_index, (start, end))) => {
+                        let word_counts = count_words_in_range(&file_path, start, end, &tokenizer_config).await?;
+                        let extension = match format {
+                            OutputFormat::Text => "txt",
+                            OutputFormat::Json => "json",
+                        };
+                        let result = format!("map_{}.{}", chunk_index, extension);
+                        write_map_result(&result, &word_counts, format).await?;
+
+                        {
+                            let state = ChunkState::Mapped { file_name: result.clone() };
+                            let mut db = progress_db.lock().await;
+                            db.chunks.insert(chunk_index, state.clone());
+                            append_progress_entry(PROGRESS_DB_PATH, chunk_index, &state).await?;
+                        }
+
+                        if file_tx.send((chunk_index, result)).await.is_err() {
+                            break; // no reduce workers left to receive
+                        }
                     }
                     None => break,
                 }
@@ -83,54 +481,122 @@ async fn map_phase(chunks: &[String], num_workers: usize) -> Result<Vec<String>,
         handles.push(handle);
     }
 
-    for handle in handles {
-        handle.await??;
-    }
-
-    Ok(Arc::try_unwrap(results).unwrap().into_inner())
+    handles
 }
 
-fn count_words(text: &str) -> HashMap<String, usize> {
-    let mut word_counts = HashMap::new();
-    for word in text.split_whitespace() {
-        let word = word.to_lowercase();
-        *word_counts.entry(word).or_insert(0) += 1;
+/// Streams the `[start, end)` byte range of `file_path` through a
+/// `BufReader`, counting words line by line per `config`. Memory use is
+/// bounded by the reader's buffer size regardless of how large the range is.
+async fn count_words_in_range(
+    file_path: &str,
+    start: u64,
+    end: u64,
+    config: &TokenizerConfig,
+) -> Result<WordMap, std::io::Error> {
+    let mut file = File::open(file_path).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+    let mut reader = BufReader::new(file);
+
+    let mut word_counts = WordMap::default();
+    let mut position = start;
+    let mut line = String::new();
+
+    while position < end {
+        line.clear();
+        let bytes_read = reader.read_line(&mut line).await?;
+        if bytes_read == 0 {
+            break;
+        }
+        position += bytes_read as u64;
+
+        for word in line.split_whitespace() {
+            if let Some(token) = normalize_token(word, config) {
+                *word_counts.entry(token).or_insert(0) += 1;
+            }
+        }
     }
-    word_counts
+
+    Ok(word_counts)
 }
 
-async fn write_map_result(file_name: &str, word_counts: &HashMap<String, usize>) -> Result<(), std::io::Error> {
+async fn write_map_result(
+    file_name: &str,
+    word_counts: &WordMap,
+    format: OutputFormat,
+) -> Result<(), std::io::Error> {
     let mut file = File::create(file_name).await?;
-    for (word, count) in word_counts {
-        file.write_all(format!("{} {}\n", word, count).as_bytes()).await?;
+
+    match format {
+        OutputFormat::Text => {
+            for (word, count) in word_counts {
+                file.write_all(format!("{} {}\n", word, count).as_bytes()).await?;
+            }
+        }
+        OutputFormat::Json => {
+            let line = serde_json::to_string(word_counts)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            file.write_all(line.as_bytes()).await?;
+            file.write_all(b"\n").await?;
+        }
     }
+
     Ok(())
 }
 
-async fn reduce_phase(map_results: Vec<String>, num_workers: usize) -> Result<HashMap<String, usize>, std::io::Error> {
-    let result_queue = Arc::new(Mutex::new(map_results));
-    let final_result = Arc::new(Mutex::new(HashMap::new()));
+/// Pulls `(chunk_index, filename)` pairs off `file_rx` as map workers (or the
+/// producer, for chunks already mapped in a prior run) produce them, and
+/// folds each into a sharded concurrent map seeded from any previously
+/// accumulated counts. The progress database is updated from each worker's
+/// own chunk counts rather than by snapshotting the shared map, so it never
+/// serializes or locks behind the concurrent increments. Returns the
+/// flattened counts alongside the list of filenames consumed, for cleanup.
+async fn reduce_phase(
+    file_rx: mpsc::Receiver<(usize, String)>,
+    num_workers: usize,
+    initial_accumulated: HashMap<String, usize>,
+    progress_db: Arc<Mutex<ProgressDb>>,
+) -> Result<(HashMap<String, usize>, Vec<String>), std::io::Error> {
+    let file_rx = Arc::new(Mutex::new(file_rx));
+    // Sharded concurrent map: workers land on disjoint shards for most words,
+    // so there's no single global lock serializing every increment.
+    let final_result: Arc<DashMap<String, usize>> = Arc::new(DashMap::from_iter(initial_accumulated));
+    let consumed_files = Arc::new(Mutex::new(Vec::new()));
 
     let mut handles = vec![];
 
     for _ in 0..num_workers {
-        let result_queue = Arc::clone(&result_queue);
+        let file_rx = Arc::clone(&file_rx);
         let final_result = Arc::clone(&final_result);
+        let consumed_files = Arc::clone(&consumed_files);
+        let progress_db = Arc::clone(&progress_db);
 
         let handle = tokio::spawn(async move {
             loop {
-                let result_file = {
-                    let mut queue = result_queue.lock().await;
-                    queue.pop()
-                };
+                let result_file = { file_rx.lock().await.recv().await };
 
                 match result_file {
-                    Some(file_name) => {
+                    Some((chunk_index, file_name)) => {
                         let word_counts = read_map_result(&file_name).await?;
-                        let mut final_result = final_result.lock().await;
-                        for (word, count) in word_counts {
-                            *final_result.entry(word).or_insert(0) += count;
+                        for (word, count) in &word_counts {
+                            *final_result.entry(word.clone()).or_insert(0) += *count;
+                        }
+
+                        // Persist exactly this chunk's own counts, never a
+                        // read of the shared `final_result`: that map is
+                        // still being mutated by other in-flight workers, so
+                        // snapshotting it could bake in counts from chunks
+                        // that aren't actually committed yet, and a crash
+                        // before they commit would double-count them on
+                        // resume.
+                        let counts: HashMap<String, usize> = word_counts.into_iter().collect();
+                        let state = ChunkState::Reduced { counts };
+                        {
+                            let mut db = progress_db.lock().await;
+                            db.chunks.insert(chunk_index, state.clone());
+                            append_progress_entry(PROGRESS_DB_PATH, chunk_index, &state).await?;
                         }
+
+                        consumed_files.lock().await.push(file_name);
                     }
                     None => break,
                 }
@@ -145,14 +611,20 @@ async fn reduce_phase(map_results: Vec<String>, num_workers: usize) -> Result<Ha
         handle.await??;
     }
 
-    Ok(Arc::try_unwrap(final_result).unwrap().into_inner())
+    let final_result = Arc::try_unwrap(final_result).unwrap();
+    let consumed_files = Arc::try_unwrap(consumed_files).unwrap().into_inner();
+    Ok((final_result.into_iter().collect(), consumed_files))
 }
 
-async fn read_map_result(file_name: &str) -> Result<HashMap<String, usize>, std::io::Error> {
+async fn read_map_result(file_name: &str) -> Result<WordMap, std::io::Error> {
+    if file_name.ends_with(".json") {
+        return read_map_result_json(file_name).await;
+    }
+
     let file = File::open(file_name).await?;
     let reader = BufReader::new(file);
     let mut lines = reader.lines();
-    let mut word_counts = HashMap::new();
+    let mut word_counts = WordMap::default();
 
     while let Some(line) = lines.next_line().await? {
         let parts: Vec<&str> = line.split_whitespace().collect();
@@ -166,15 +638,53 @@ async fn read_map_result(file_name: &str) -> Result<HashMap<String, usize>, std:
     Ok(word_counts)
 }
 
-async fn write_final_result(word_counts: &HashMap<String, usize>) -> Result<(), std::io::Error> {
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .open("final_result.txt")
-        .await?;
+async fn read_map_result_json(file_name: &str) -> Result<WordMap, std::io::Error> {
+    let file = File::open(file_name).await?;
+    let reader = BufReader::new(file);
+    let mut lines = reader.lines();
+    let mut word_counts = WordMap::default();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let parsed: HashMap<String, usize> = serde_json::from_str(&line)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        word_counts.extend(parsed);
+    }
 
-    for (word, count) in word_counts {
-        file.write_all(format!("{} {}\n", word, count).as_bytes()).await?;
+    Ok(word_counts)
+}
+
+async fn write_final_result(
+    word_counts: &HashMap<String, usize>,
+    format: OutputFormat,
+) -> Result<(), std::io::Error> {
+    match format {
+        OutputFormat::Text => {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open("final_result.txt")
+                .await?;
+
+            for (word, count) in word_counts {
+                file.write_all(format!("{} {}\n", word, count).as_bytes()).await?;
+            }
+        }
+        OutputFormat::Json => {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open("final_result.json")
+                .await?;
+
+            let pretty = serde_json::to_string_pretty(word_counts)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            file.write_all(pretty.as_bytes()).await?;
+        }
     }
 
     Ok(())
@@ -197,4 +707,149 @@ async fn cleanup_intermediate_files(map_results: &[String]) -> Result<(), std::i
         }
     }
     Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `contents` to a uniquely-named file under the OS temp dir and
+    /// returns its path, for tests that need a real `File` to seek/read.
+    async fn write_temp_file(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "map-oxidize-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        tokio::fs::write(&path, contents).await.unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn accumulated_ignores_chunks_not_yet_reduced() {
+        // Regression test for the resume bug where a chunk still `Mapped`
+        // (its reduce worker hasn't committed yet) got folded into the
+        // persisted total anyway, because the total was a snapshot of a
+        // shared map other workers were still mutating.
+        let mut db = ProgressDb::default();
+        db.chunks.insert(
+            0,
+            ChunkState::Reduced {
+                counts: HashMap::from([("hello".to_string(), 3)]),
+            },
+        );
+        db.chunks.insert(1, ChunkState::Mapped { file_name: "map_1.txt".to_string() });
+
+        let accumulated = db.accumulated();
+
+        assert_eq!(accumulated.get("hello"), Some(&3));
+        assert_eq!(accumulated.len(), 1, "the still-Mapped chunk must not contribute");
+    }
+
+    #[test]
+    fn accumulated_sums_each_reduced_chunk_exactly_once() {
+        let mut db = ProgressDb::default();
+        db.chunks.insert(
+            0,
+            ChunkState::Reduced {
+                counts: HashMap::from([("hello".to_string(), 2)]),
+            },
+        );
+        db.chunks.insert(
+            1,
+            ChunkState::Reduced {
+                counts: HashMap::from([("hello".to_string(), 5), ("world".to_string(), 1)]),
+            },
+        );
+
+        let accumulated = db.accumulated();
+
+        assert_eq!(accumulated.get("hello"), Some(&7));
+        assert_eq!(accumulated.get("world"), Some(&1));
+    }
+
+    #[tokio::test]
+    async fn progress_journal_roundtrips_through_append_and_load() {
+        let path = write_temp_file("journal-roundtrip", "").await;
+        tokio::fs::remove_file(&path).await.unwrap(); // start from "doesn't exist yet"
+
+        let mapped = ChunkState::Mapped { file_name: "map_0.txt".to_string() };
+        append_progress_entry(&path, 0, &mapped).await.unwrap();
+        let reduced = ChunkState::Reduced {
+            counts: HashMap::from([("a".to_string(), 1)]),
+        };
+        append_progress_entry(&path, 1, &reduced).await.unwrap();
+
+        let db = load_progress_db(&path).await.unwrap();
+
+        assert_eq!(db.state_of(0), mapped);
+        assert_eq!(db.state_of(1), reduced);
+        assert_eq!(db.accumulated().get("a"), Some(&1));
+
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn next_line_boundary_never_splits_a_word() {
+        let contents = "the quick brown\nfox jumps over\nthe lazy dog\n";
+        let path = write_temp_file("boundary-mid-word", contents).await;
+        let mut file = File::open(&path).await.unwrap();
+        let file_len = contents.len() as u64;
+
+        // "the quick brown\n" is 16 bytes; aim inside "quick" so a naive
+        // split would cut the word in half.
+        let approx = 8;
+        let boundary = next_line_boundary(&mut file, approx, file_len).await.unwrap();
+
+        assert_eq!(boundary, 16, "boundary should land just after the newline");
+        assert_eq!(&contents[boundary as usize - 1..boundary as usize], "\n");
+
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn next_line_boundary_clamps_to_file_len_past_last_newline() {
+        let contents = "only one line with no trailing newline";
+        let path = write_temp_file("boundary-no-newline", contents).await;
+        let mut file = File::open(&path).await.unwrap();
+        let file_len = contents.len() as u64;
+
+        let boundary = next_line_boundary(&mut file, 5, file_len).await.unwrap();
+
+        assert_eq!(boundary, file_len);
+
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn next_line_boundary_passes_through_edges_untouched() {
+        let contents = "abc\ndef\n";
+        let path = write_temp_file("boundary-edges", contents).await;
+        let mut file = File::open(&path).await.unwrap();
+        let file_len = contents.len() as u64;
+
+        assert_eq!(next_line_boundary(&mut file, 0, file_len).await.unwrap(), 0);
+        assert_eq!(
+            next_line_boundary(&mut file, file_len, file_len).await.unwrap(),
+            file_len
+        );
+
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+
+    #[test]
+    fn stop_words_fold_with_the_same_policy_as_tokens() {
+        let config = TokenizerConfig {
+            strip_punctuation: false,
+            fold_case: false,
+            stop_words: Arc::new(HashSet::from(["The".to_string()])),
+        };
+
+        // With case folding off, a stop word loaded as-is must still match a
+        // token of the same original case.
+        assert_eq!(normalize_token("The", &config), None);
+        // ...but not a differently-cased token: that's a deliberate
+        // consequence of turning case folding off, not a bug.
+        assert_eq!(normalize_token("the", &config), Some("the".to_string()));
+    }
 }
\ No newline at end of file